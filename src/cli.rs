@@ -1,28 +1,76 @@
+use crate::program::commands;
 use crate::CliError;
 use clap::{crate_description, crate_name, crate_version, App, Arg};
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Human,
+    Json,
+    Kv,
+}
+
+impl Format {
+    fn parse(s: &str) -> Format {
+        match s {
+            "json" => Format::Json,
+            "kv" => Format::Kv,
+            _ => Format::Human,
+        }
+    }
+}
+
 pub struct Config {
     pub command: String,
+    pub field: Option<String>,
+    pub full: bool,
+    pub format: Format,
+    pub watch: bool,
+    pub threshold: i64,
 }
 
 impl Config {
     pub fn new() -> Result<Self, CliError> {
-        let matches = App::new(crate_name!())
+        let mut app = App::new(crate_name!())
             .version(crate_version!())
             .about(crate_description!())
             .arg(
-                Arg::new("command")
-                    .default_value("login")
-                    // .hide_default_value(true)
-                    .index(1)
-                    .help("Command to run"),
-            )
-            .get_matches();
+                Arg::new("format")
+                    .long("format")
+                    .takes_value(true)
+                    .possible_values(&["human", "json", "kv"])
+                    .default_value("human")
+                    .global(true)
+                    .help("Output format"),
+            );
 
-        let command = matches.value_of("command").unwrap();
+        for command in commands::all() {
+            app = app.subcommand(command.configure(App::new(command.name())));
+        }
+
+        let matches = app.get_matches();
+
+        let format = Format::parse(matches.value_of("format").unwrap());
+        let command = matches
+            .subcommand_name()
+            .unwrap_or("login")
+            .to_string();
+        let sub = matches.subcommand_matches(&command);
+
+        let field = sub.and_then(|m| m.value_of("field")).map(str::to_string);
+        let full = sub.map(|m| m.is_present("full")).unwrap_or(false);
+        let watch = sub.map(|m| m.is_present("watch")).unwrap_or(false);
+        let threshold = sub
+            .and_then(|m| m.value_of("threshold"))
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(7);
 
         Ok(Config {
-            command: command.to_string(),
+            command,
+            field,
+            full,
+            format,
+            watch,
+            threshold,
         })
     }
-}
\ No newline at end of file
+}
@@ -1,4 +1,5 @@
 use std::io::Write;
+use std::time::Duration;
 
 use crate::cli::Config;
 use chrono::DateTime;
@@ -10,23 +11,25 @@ use cli_42::Mode;
 use cli_42::Session;
 use cli_42::SessionError;
 use directories::BaseDirs;
+use tokio::net::UnixStream;
 use url::Url;
 
-pub enum Command {
-    Id,
-    Me,
-    Email,
-    Login,
-    CorrectionPoint,
-    Wallet,
-    Blackhole,
-}
+mod agent;
+pub mod commands;
+mod http;
+mod output;
+mod secrets;
+
+use output::Output;
 
-#[derive(Debug)]
 pub struct Program {
     session: Session,
+    agent: Option<UnixStream>,
+    base_url: String,
+    pub client_cert_path: Option<String>,
     pub token: Option<TokenInfo>,
     pub config: Config,
+    http_client: reqwest::Client,
 }
 
 impl Program {
@@ -40,30 +43,114 @@ impl Program {
                 }
             }
         }
+        sync_config_secret()?;
+
+        let network = network_settings();
+        let http_client = http::build_client(network.client_cert_path.as_deref())?;
+
+        // a running agent already owns an authenticated session, so skip the
+        // credentials flow entirely and just borrow its socket
+        if let Some(stream) = agent::try_connect().await {
+            return Ok(Program {
+                session: read_session_from_config()?,
+                agent: Some(stream),
+                base_url: network.base_url,
+                client_cert_path: network.client_cert_path,
+                token: None,
+                config,
+                http_client,
+            });
+        }
+
         let program = Program {
             session: Session::new(Some(Mode::Credentials)).await?,
+            agent: None,
+            base_url: network.base_url,
+            client_cert_path: network.client_cert_path,
             token: None,
             config,
+            http_client,
         };
         Ok(program)
     }
 
     pub async fn call(&mut self, url: &str) -> Result<String, SessionError> {
-        let res = self.session.call(url).await?;
-        Ok(res)
+        if let Some(stream) = self.agent.as_mut() {
+            let req = agent::AgentRequest::Call {
+                url: url.to_string(),
+            };
+            return match agent::send_request(stream, &req).await? {
+                agent::AgentResponse::Ok(body) => Ok(body),
+                agent::AgentResponse::Locked => {
+                    Err(std::io::Error::new(std::io::ErrorKind::WouldBlock, "agent is locked").into())
+                }
+                agent::AgentResponse::Err(msg) => {
+                    Err(std::io::Error::new(std::io::ErrorKind::Other, msg).into())
+                }
+            };
+        }
+        http::fetch(&self.http_client, &mut self.session, url).await
     }
 
-    pub async fn run_program(&mut self, command: Command) -> Result<(), SessionError> {
+    pub async fn run_program(&mut self) -> Result<(), SessionError> {
+        let name = self.config.command.clone();
+        let command = commands::all()
+            .into_iter()
+            .find(|command| command.name() == name)
+            .ok_or_else(|| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!("unknown command: {}", name),
+                )
+            })?;
+
+        if !command.needs_user() {
+            return command.run(self, None).await;
+        }
+
         let tmp = self.get_user_with_login().await?;
         let user = self.get_user_info_with_id(tmp.id).await?;
-        match command {
-            Command::Id => self.id(&tmp).await?,
-            Command::Me => self.me(&user).await?,
-            Command::Email => self.email(&user).await?,
-            Command::Login => self.login(&user).await?,
-            Command::CorrectionPoint => self.correction_point(&user).await?,
-            Command::Wallet => self.wallet(&user).await?,
-            Command::Blackhole => self.blackhole(&user).await?,
+        let ctx = commands::Context {
+            tmp: &tmp,
+            user: &user,
+        };
+        command.run(self, Some(&ctx)).await
+    }
+
+    async fn run_agent(&mut self) -> Result<(), SessionError> {
+        sync_config_secret()?;
+        let session = Session::new(Some(Mode::Credentials)).await?;
+        agent::run(
+            session,
+            Duration::from_secs(agent_timeout_secs()),
+            self.client_cert_path.clone(),
+        )
+        .await
+    }
+
+    async fn lock(&mut self) -> Result<(), SessionError> {
+        match self.agent.as_mut() {
+            Some(stream) => {
+                agent::send_request(stream, &agent::AgentRequest::Lock).await?;
+                println!("Agent locked.");
+            }
+            None => eprintln!("No agent is running."),
+        }
+        Ok(())
+    }
+
+    async fn unlock(&mut self) -> Result<(), SessionError> {
+        match self.agent.as_mut() {
+            Some(stream) => {
+                let secret = secrets::prompt("Enter client secret to unlock: ")?;
+                let req = agent::AgentRequest::Unlock { secret };
+                match agent::send_request(stream, &req).await? {
+                    agent::AgentResponse::Ok(_) => println!("Agent unlocked."),
+                    agent::AgentResponse::Err(msg) => eprintln!("Unlock failed: {}", msg),
+                    agent::AgentResponse::Locked => {}
+                }
+            }
+            None => eprintln!("No agent is running."),
         }
         Ok(())
     }
@@ -71,9 +158,9 @@ impl Program {
 
 impl Program {
     async fn get_user_with_login(&mut self) -> Result<user::UserElement, SessionError> {
-        let url = "https://api.intra.42.fr/v2/users";
+        let url = format!("{}/users", self.base_url);
         let url = Url::parse_with_params(
-            url,
+            &url,
             &[
                 ("client_id", self.session.get_client_id()),
                 ("filter[login]", self.session.get_login()),
@@ -86,7 +173,7 @@ impl Program {
     }
 
     async fn get_user_info_with_id(&mut self, id: i64) -> Result<me::Me, SessionError> {
-        let url = format!("https://api.intra.42.fr/v2/users/{}", id);
+        let url = format!("{}/users/{}", self.base_url, id);
         let url = Url::parse_with_params(&url, &[("client_id", self.session.get_client_id())])?;
 
         let res = self.call(url.as_str()).await?;
@@ -95,68 +182,148 @@ impl Program {
     }
 
     async fn me(&mut self, user: &me::Me) -> Result<(), SessionError> {
-        let title = if user.titles.is_empty() {
-            ""
-        } else {
-            user.titles[0].name.split(' ').next().unwrap_or("")
-        };
-        println!("{} | {} {}", user.displayname, title, user.login);
-        self.wallet(user).await?;
-        self.correction_point(user).await?;
-        println!("{:20}{}", "Cursus", user.cursus_users[1].cursus.name);
-        println!(
-            "{:20}{}",
+        if self.config.format == crate::cli::Format::Human {
+            let title = if user.titles.is_empty() {
+                ""
+            } else {
+                user.titles[0].name.split(' ').next().unwrap_or("")
+            };
+            println!("{} | {} {}", user.displayname, title, user.login);
+        }
+        let mut out = Output::new(self.config.format);
+        out.push("wallet", "Wallet", user.wallet);
+        out.push("correction_point", "Correction point", user.correction_point);
+        out.push("cursus", "Cursus", user.cursus_users[1].cursus.name.clone());
+        out.push(
+            "grade",
             "Grade",
-            user.cursus_users[1]
-                .grade
-                .as_ref()
-                .unwrap_or(&"".to_string())
+            user.cursus_users[1].grade.clone().unwrap_or_default(),
         );
-        self.blackhole(user).await?;
+        push_blackhole(&mut out, user)?;
+        out.print();
         Ok(())
     }
 
     async fn email(&mut self, user: &me::Me) -> Result<(), SessionError> {
-        println!("{:20}{}", "Email", user.email);
+        let mut out = Output::new(self.config.format);
+        out.push("email", "Email", user.email.clone());
+        out.print();
         Ok(())
     }
 
     async fn wallet(&mut self, user: &me::Me) -> Result<(), SessionError> {
-        println!("{:20}{}", "Wallet", user.wallet);
+        let mut out = Output::new(self.config.format);
+        out.push("wallet", "Wallet", user.wallet);
+        out.print();
         Ok(())
     }
 
     async fn id(&mut self, tmp: &UserElement) -> Result<(), SessionError> {
-        println!("{:20}{}", "ID", tmp.id);
+        let mut out = Output::new(self.config.format);
+        out.push("id", "ID", tmp.id);
+        out.print();
         Ok(())
     }
 
     async fn login(&mut self, user: &me::Me) -> Result<(), SessionError> {
-        println!("{:20}{}", "Login", user.login);
+        let mut out = Output::new(self.config.format);
+        out.push("login", "Login", user.login.clone());
+        out.print();
         Ok(())
     }
 
     async fn correction_point(&mut self, user: &me::Me) -> Result<(), SessionError> {
-        println!("{:20}{}", "Correction point", user.correction_point);
+        let mut out = Output::new(self.config.format);
+        out.push("correction_point", "Correction point", user.correction_point);
+        out.print();
+        Ok(())
+    }
+
+    async fn get(&mut self, ctx: &commands::Context<'_>) -> Result<(), SessionError> {
+        if self.config.full {
+            return self.full(ctx).await;
+        }
+        let path = self.config.field.clone().unwrap_or_default();
+        let value = merged_user_value(ctx)?;
+        let mut out = Output::new(self.config.format);
+        match resolve_path(&value, &path) {
+            Some(found) => out.push(&path, &path, found.clone()),
+            None => eprintln!("no such field: {}", path),
+        }
+        out.print();
+        Ok(())
+    }
+
+    async fn full(&mut self, ctx: &commands::Context<'_>) -> Result<(), SessionError> {
+        let value = merged_user_value(ctx)?;
+        let mut out = Output::new(self.config.format);
+        if let serde_json::Value::Object(map) = value {
+            for (key, val) in map {
+                out.push(&key, &key, val);
+            }
+        }
+        out.print();
         Ok(())
     }
 
     async fn blackhole(&mut self, user: &me::Me) -> Result<(), SessionError> {
-        let utc = Utc::now();
-        let utc2 = user.cursus_users[1]
-            .blackholed_at
-            .as_ref()
-            .unwrap_or(&"".to_string())
-            .parse::<DateTime<Utc>>()?;
-        println!(
-            "{:20}{}",
-            "Blackhole",
-            utc2.signed_duration_since(utc).num_days()
-        );
+        let mut out = Output::new(self.config.format);
+        push_blackhole(&mut out, user)?;
+        out.print();
         Ok(())
     }
 }
 
+/// Whole days remaining before `user` hits the blackhole, truncated toward
+/// zero (so negative only once more than a full day past).
+/// Shared by the one-shot `blackhole` command and the `--watch` loop so they
+/// can't drift apart on how they read `cursus_users`.
+fn blackhole_days(user: &me::Me) -> Result<i64, SessionError> {
+    let blackholed_at = user.cursus_users[1]
+        .blackholed_at
+        .clone()
+        .unwrap_or_default();
+    let at = blackholed_at.parse::<DateTime<Utc>>()?;
+    Ok(at.signed_duration_since(Utc::now()).num_days())
+}
+
+fn push_blackhole(out: &mut Output, user: &me::Me) -> Result<(), SessionError> {
+    let blackholed_at = user.cursus_users[1]
+        .blackholed_at
+        .clone()
+        .unwrap_or_default();
+    out.push("blackhole_days", "Blackhole", blackhole_days(user)?);
+    out.push("blackholed_at", "Blackholed at", blackholed_at);
+    Ok(())
+}
+
+fn resolve_path<'a>(value: &'a serde_json::Value, path: &str) -> Option<&'a serde_json::Value> {
+    let mut current = value;
+    for part in path.split('.') {
+        current = match part.parse::<usize>() {
+            Ok(index) => current.get(index)?,
+            Err(_) => current.get(part)?,
+        };
+    }
+    Some(current)
+}
+
+/// `get`/`full` are meant to expose every field `icp` knows about a user, but
+/// `ctx.user` (`me::Me`) alone doesn't carry everything `ctx.tmp`
+/// (`UserElement`, from the `/users?filter[login]` lookup) does, so this
+/// merges the two, preferring `me::Me`'s value on any key they share.
+fn merged_user_value(ctx: &commands::Context<'_>) -> Result<serde_json::Value, SessionError> {
+    let mut value = serde_json::to_value(ctx.user)?;
+    if let serde_json::Value::Object(tmp_map) = serde_json::to_value(ctx.tmp)? {
+        if let serde_json::Value::Object(map) = &mut value {
+            for (key, val) in tmp_map {
+                map.entry(key).or_insert(val);
+            }
+        }
+    }
+    Ok(value)
+}
+
 fn check_if_config_file_exists() -> bool {
     if let Some(dir) = BaseDirs::new() {
         let path = dir.config_dir().join("config.toml");
@@ -185,10 +352,10 @@ fn create_config_toml() -> Result<(), SessionError> {
         stdin.read_line(&mut line)?;
         writeln!(&mut file, "client_id=\"{}\"", line.trim())?;
         line.clear();
-        println!("Enter client secret: ");
-        stdin.read_line(&mut line)?;
-        writeln!(&mut file, "client_secret=\"{}\"", line.trim())?;
-        line.clear();
+
+        let secret = secrets::prompt("Enter client secret: ")?;
+        secrets::store(&secret)?;
+
         println!("Enter intra login: ");
         stdin.read_line(&mut line)?;
         writeln!(&mut file, "login=\"{}\"", line.trim())?;
@@ -206,6 +373,8 @@ fn check_config_toml() -> Result<bool, SessionError> {
         let tmp = std::fs::read_to_string(path);
         match tmp {
             Ok(content) => {
+                let content = migrate_legacy_secret(&content)?;
+                let content = with_keyring_secret(content)?;
                 let config: Session = toml::from_str(&content)?;
                 if !(check_client(&config)) {
                     return Ok(false);
@@ -230,6 +399,119 @@ fn check_config_toml() -> Result<bool, SessionError> {
     Ok(true)
 }
 
+fn read_session_from_config() -> Result<Session, SessionError> {
+    let dir = BaseDirs::new().ok_or(SessionError::BaseDirsNewError)?;
+    let path = dir.config_dir().join("config.toml");
+    let content = std::fs::read_to_string(path)?;
+    let content = migrate_legacy_secret(&content)?;
+    let content = with_keyring_secret(content)?;
+    let session: Session = toml::from_str(&content)?;
+    Ok(session)
+}
+
+/// `Session::new` reads config.toml straight off disk, so on every startup
+/// (not just the first one) this migrates a legacy plaintext secret into the
+/// keyring and writes the keyring-held secret back into config.toml, so the
+/// credentials flow always finds one whether the file predates the keyring
+/// or was just created without it.
+fn sync_config_secret() -> Result<(), SessionError> {
+    let dir = BaseDirs::new().ok_or(SessionError::BaseDirsNewError)?;
+    let path = dir.config_dir().join("config.toml");
+    let content = std::fs::read_to_string(&path)?;
+    let content = migrate_legacy_secret(&content)?;
+    let content = with_keyring_secret(content)?;
+    std::fs::write(path, content)?;
+    Ok(())
+}
+
+/// Merges the keyring-held client secret into an on-disk config.toml that no
+/// longer carries it, so `Session`'s deserialization stays untouched.
+fn with_keyring_secret(content: String) -> Result<String, SessionError> {
+    match secrets::load()? {
+        Some(secret) => Ok(format!("{}\nclient_secret=\"{}\"\n", content, secret)),
+        None => Ok(content),
+    }
+}
+
+/// Detects a pre-keyring config.toml that still has `client_secret` in
+/// cleartext, moves it into the keyring, and rewrites the file without it.
+fn migrate_legacy_secret(content: &str) -> Result<String, SessionError> {
+    #[derive(serde::Deserialize)]
+    struct LegacyConfig {
+        client_secret: Option<String>,
+    }
+
+    let legacy: LegacyConfig =
+        toml::from_str(content).unwrap_or(LegacyConfig { client_secret: None });
+
+    let secret = match legacy.client_secret {
+        Some(secret) => secret,
+        None => return Ok(content.to_string()),
+    };
+
+    if secrets::load()?.is_none() {
+        secrets::store(&secret)?;
+    }
+
+    let cleaned = strip_client_secret_line(content);
+    if let Some(dir) = BaseDirs::new() {
+        std::fs::write(dir.config_dir().join("config.toml"), &cleaned)?;
+    }
+    Ok(cleaned)
+}
+
+fn strip_client_secret_line(content: &str) -> String {
+    let mut cleaned: String = content
+        .lines()
+        .filter(|line| !line.trim_start().starts_with("client_secret"))
+        .collect::<Vec<_>>()
+        .join("\n");
+    cleaned.push('\n');
+    cleaned
+}
+
+const DEFAULT_BASE_URL: &str = "https://api.intra.42.fr/v2";
+
+struct NetworkSettings {
+    base_url: String,
+    client_cert_path: Option<String>,
+}
+
+fn network_settings() -> NetworkSettings {
+    #[derive(serde::Deserialize, Default)]
+    struct RawSettings {
+        base_url: Option<String>,
+        client_cert_path: Option<String>,
+    }
+
+    let raw = BaseDirs::new()
+        .and_then(|dir| std::fs::read_to_string(dir.config_dir().join("config.toml")).ok())
+        .and_then(|content| toml::from_str::<RawSettings>(&content).ok())
+        .unwrap_or_default();
+
+    NetworkSettings {
+        base_url: raw.base_url.unwrap_or_else(|| DEFAULT_BASE_URL.to_string()),
+        client_cert_path: raw.client_cert_path,
+    }
+}
+
+fn agent_timeout_secs() -> u64 {
+    #[derive(serde::Deserialize)]
+    struct AgentSettings {
+        #[serde(default = "default_agent_timeout")]
+        agent_timeout: u64,
+    }
+    fn default_agent_timeout() -> u64 {
+        agent::DEFAULT_TIMEOUT_SECS
+    }
+
+    BaseDirs::new()
+        .and_then(|dir| std::fs::read_to_string(dir.config_dir().join("config.toml")).ok())
+        .and_then(|content| toml::from_str::<AgentSettings>(&content).ok())
+        .map(|settings| settings.agent_timeout)
+        .unwrap_or(agent::DEFAULT_TIMEOUT_SECS)
+}
+
 fn check_client(session: &Session) -> bool {
     let client_id = session.get_client_id();
     let client_secret = session.get_client_secret();
@@ -241,3 +523,24 @@ fn check_client(session: &Session) -> bool {
     }
     true
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolve_path_walks_dotted_and_indexed_segments() {
+        let value: serde_json::Value =
+            serde_json::json!({"cursus_users": [{"grade": "Member"}, {"grade": "Learner"}]});
+        assert_eq!(
+            resolve_path(&value, "cursus_users.1.grade"),
+            Some(&serde_json::json!("Learner"))
+        );
+    }
+
+    #[test]
+    fn resolve_path_returns_none_for_unknown_field() {
+        let value: serde_json::Value = serde_json::json!({"login": "bagmeg"});
+        assert_eq!(resolve_path(&value, "nope"), None);
+    }
+}
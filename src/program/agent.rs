@@ -0,0 +1,198 @@
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use cli_42::{Session, SessionError};
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::RwLock;
+
+use super::{http, secrets};
+
+/// How long the agent keeps a session warm after the last served `Call` or
+/// successful `Unlock` (a `Lock` does not count), unless overridden by
+/// `agent_timeout` in config.toml.
+pub const DEFAULT_TIMEOUT_SECS: u64 = 10 * 60;
+
+/// Length-prefixed messages above this are refused before the buffer is
+/// allocated, so a peer can't make the agent allocate on an unbounded
+/// attacker-controlled size.
+const MAX_MESSAGE_BYTES: u32 = 8 * 1024 * 1024;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub enum AgentRequest {
+    Call { url: String },
+    Lock,
+    Unlock { secret: String },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub enum AgentResponse {
+    Ok(String),
+    Locked,
+    Err(String),
+}
+
+struct AgentState {
+    session: Session,
+    http_client: reqwest::Client,
+    locked: bool,
+    last_activity: Instant,
+}
+
+pub fn socket_path() -> Result<PathBuf, SessionError> {
+    let dir = directories::BaseDirs::new().ok_or(SessionError::BaseDirsNewError)?;
+    let runtime_dir = dir.runtime_dir().unwrap_or_else(|| dir.cache_dir());
+    Ok(runtime_dir.join("icp-agent.sock"))
+}
+
+/// Connects to a running agent, if any. Returns `None` when nothing is
+/// listening, so the caller can fall back to a direct session.
+pub async fn try_connect() -> Option<UnixStream> {
+    let path = socket_path().ok()?;
+    UnixStream::connect(path).await.ok()
+}
+
+pub async fn send_request(
+    stream: &mut UnixStream,
+    req: &AgentRequest,
+) -> Result<AgentResponse, SessionError> {
+    write_message(stream, req).await?;
+    read_message(stream).await
+}
+
+pub async fn run(
+    session: Session,
+    timeout: Duration,
+    client_cert_path: Option<String>,
+) -> Result<(), SessionError> {
+    let path = socket_path()?;
+    let listener = bind(&path)?;
+    let http_client = http::build_client(client_cert_path.as_deref())?;
+
+    let state = Arc::new(RwLock::new(AgentState {
+        session,
+        http_client,
+        locked: false,
+        last_activity: Instant::now(),
+    }));
+
+    let timer_state = state.clone();
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            if timer_state.read().await.last_activity.elapsed() >= timeout {
+                break;
+            }
+        }
+        std::process::exit(0);
+    });
+
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let state = state.clone();
+        tokio::spawn(serve_connection(stream, state));
+    }
+}
+
+/// A client opens the socket once and keeps reusing it for every `call()` it
+/// makes, so this has to keep serving requests off the same stream until the
+/// client disconnects rather than closing after the first one.
+async fn serve_connection(mut stream: UnixStream, state: Arc<RwLock<AgentState>>) {
+    loop {
+        let req = match read_message::<AgentRequest>(&mut stream).await {
+            Ok(req) => req,
+            Err(_) => return,
+        };
+        let resp = handle(&state, req).await;
+        if write_message(&mut stream, &resp).await.is_err() {
+            return;
+        }
+    }
+}
+
+fn bind(path: &Path) -> Result<UnixListener, SessionError> {
+    if path.exists() {
+        // a crashed agent can leave its socket file behind; if nothing
+        // answers on it, it's stale and safe to unlink and rebind
+        if std::os::unix::net::UnixStream::connect(path).is_err() {
+            std::fs::remove_file(path)?;
+        }
+    }
+    let listener = UnixListener::bind(path)?;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+    Ok(listener)
+}
+
+async fn handle(state: &Arc<RwLock<AgentState>>, req: AgentRequest) -> AgentResponse {
+    let mut guard = state.write().await;
+    match req {
+        AgentRequest::Call { url } => {
+            if guard.locked {
+                return AgentResponse::Locked;
+            }
+            guard.last_activity = Instant::now();
+            match http::fetch(&guard.http_client, &mut guard.session, &url).await {
+                Ok(body) => AgentResponse::Ok(body),
+                Err(e) => AgentResponse::Err(e.to_string()),
+            }
+        }
+        AgentRequest::Lock => {
+            guard.locked = true;
+            AgentResponse::Ok(String::new())
+        }
+        AgentRequest::Unlock { secret } => match secrets::load() {
+            Ok(Some(stored)) if stored == secret => {
+                guard.locked = false;
+                guard.last_activity = Instant::now();
+                AgentResponse::Ok(String::new())
+            }
+            Ok(_) => AgentResponse::Err("incorrect secret".to_string()),
+            Err(e) => AgentResponse::Err(e.to_string()),
+        },
+    }
+}
+
+async fn write_message<T: Serialize>(stream: &mut UnixStream, msg: &T) -> Result<(), SessionError> {
+    let payload = serde_json::to_vec(msg)?;
+    stream.write_u32(payload.len() as u32).await?;
+    stream.write_all(&payload).await?;
+    Ok(())
+}
+
+async fn read_message<T: for<'de> Deserialize<'de>>(
+    stream: &mut UnixStream,
+) -> Result<T, SessionError> {
+    let len = stream.read_u32().await?;
+    if len > MAX_MESSAGE_BYTES {
+        return Err(
+            std::io::Error::new(std::io::ErrorKind::InvalidData, "message too large").into(),
+        );
+    }
+    let mut buf = vec![0u8; len as usize];
+    stream.read_exact(&mut buf).await?;
+    Ok(serde_json::from_slice(&buf)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn write_then_read_message_roundtrips() {
+        let (mut a, mut b) = UnixStream::pair().unwrap();
+        write_message(&mut a, &AgentRequest::Lock).await.unwrap();
+        let req: AgentRequest = read_message(&mut b).await.unwrap();
+        assert!(matches!(req, AgentRequest::Lock));
+    }
+
+    #[tokio::test]
+    async fn read_message_rejects_oversized_length_prefix() {
+        let (mut a, mut b) = UnixStream::pair().unwrap();
+        a.write_u32(MAX_MESSAGE_BYTES + 1).await.unwrap();
+        let result: Result<AgentRequest, SessionError> = read_message(&mut b).await;
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,230 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use clap::{App, Arg};
+use cli_42::results::me;
+use cli_42::results::user::UserElement;
+use cli_42::SessionError;
+
+use super::Program;
+
+/// The two lookups every command whose `needs_user()` is `true` is handed
+/// once `run_program` has fetched them, so such a command never has to make
+/// its own network round trip; the other commands get `None`.
+pub struct Context<'a> {
+    pub tmp: &'a UserElement,
+    pub user: &'a me::Me,
+}
+
+#[async_trait]
+pub trait Command: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    fn configure<'a>(&self, app: App<'a>) -> App<'a> {
+        app.about(self.name())
+    }
+
+    /// Set to `false` for commands (like the agent daemon) that must not
+    /// trigger the usual login/user lookup before running.
+    fn needs_user(&self) -> bool {
+        true
+    }
+
+    async fn run(&self, program: &mut Program, ctx: Option<&Context<'_>>) -> Result<(), SessionError>;
+}
+
+pub fn all() -> Vec<Box<dyn Command>> {
+    vec![
+        Box::new(Id),
+        Box::new(Me),
+        Box::new(Email),
+        Box::new(Login),
+        Box::new(CorrectionPoint),
+        Box::new(Wallet),
+        Box::new(Blackhole),
+        Box::new(Get),
+        Box::new(Agent),
+        Box::new(Lock),
+        Box::new(Unlock),
+    ]
+}
+
+struct Id;
+#[async_trait]
+impl Command for Id {
+    fn name(&self) -> &'static str {
+        "id"
+    }
+    async fn run(&self, program: &mut Program, ctx: Option<&Context<'_>>) -> Result<(), SessionError> {
+        program.id(ctx.unwrap().tmp).await
+    }
+}
+
+struct Me;
+#[async_trait]
+impl Command for Me {
+    fn name(&self) -> &'static str {
+        "me"
+    }
+    async fn run(&self, program: &mut Program, ctx: Option<&Context<'_>>) -> Result<(), SessionError> {
+        program.me(ctx.unwrap().user).await
+    }
+}
+
+struct Email;
+#[async_trait]
+impl Command for Email {
+    fn name(&self) -> &'static str {
+        "email"
+    }
+    async fn run(&self, program: &mut Program, ctx: Option<&Context<'_>>) -> Result<(), SessionError> {
+        program.email(ctx.unwrap().user).await
+    }
+}
+
+struct Login;
+#[async_trait]
+impl Command for Login {
+    fn name(&self) -> &'static str {
+        "login"
+    }
+    async fn run(&self, program: &mut Program, ctx: Option<&Context<'_>>) -> Result<(), SessionError> {
+        program.login(ctx.unwrap().user).await
+    }
+}
+
+struct CorrectionPoint;
+#[async_trait]
+impl Command for CorrectionPoint {
+    fn name(&self) -> &'static str {
+        "correction-point"
+    }
+    async fn run(&self, program: &mut Program, ctx: Option<&Context<'_>>) -> Result<(), SessionError> {
+        program.correction_point(ctx.unwrap().user).await
+    }
+}
+
+struct Wallet;
+#[async_trait]
+impl Command for Wallet {
+    fn name(&self) -> &'static str {
+        "wallet"
+    }
+    async fn run(&self, program: &mut Program, ctx: Option<&Context<'_>>) -> Result<(), SessionError> {
+        program.wallet(ctx.unwrap().user).await
+    }
+}
+
+struct Get;
+#[async_trait]
+impl Command for Get {
+    fn name(&self) -> &'static str {
+        "get"
+    }
+    fn configure<'a>(&self, app: App<'a>) -> App<'a> {
+        app.about("Print a single field, or every field with --full")
+            .arg(
+                Arg::new("field")
+                    .index(1)
+                    .help("Dotted/indexed field path, e.g. cursus_users.1.grade"),
+            )
+            .arg(
+                Arg::new("full")
+                    .long("full")
+                    .takes_value(false)
+                    .help("Print every known field instead of a single one"),
+            )
+    }
+    async fn run(&self, program: &mut Program, ctx: Option<&Context<'_>>) -> Result<(), SessionError> {
+        program.get(ctx.unwrap()).await
+    }
+}
+
+struct Blackhole;
+#[async_trait]
+impl Command for Blackhole {
+    fn name(&self) -> &'static str {
+        "blackhole"
+    }
+    fn configure<'a>(&self, app: App<'a>) -> App<'a> {
+        app.about("Show days left before blackhole")
+            .arg(
+                Arg::new("watch")
+                    .long("watch")
+                    .takes_value(false)
+                    .help("Keep running and notify as the deadline approaches"),
+            )
+            .arg(
+                Arg::new("threshold")
+                    .long("threshold")
+                    .takes_value(true)
+                    .default_value("7")
+                    .help("Notify once the remaining days fall under this"),
+            )
+    }
+    async fn run(&self, program: &mut Program, ctx: Option<&Context<'_>>) -> Result<(), SessionError> {
+        let user = ctx.unwrap().user;
+        program.blackhole(user).await?;
+        if program.config.watch {
+            watch(user, program.config.threshold).await?;
+        }
+        Ok(())
+    }
+}
+
+async fn watch(user: &me::Me, threshold: i64) -> Result<(), SessionError> {
+    loop {
+        let remaining = super::blackhole_days(user)?;
+
+        if remaining < threshold {
+            let _ = notify_rust::Notification::new()
+                .summary("42 blackhole approaching")
+                .body(&format!("{} day(s) left before blackhole", remaining))
+                .show();
+        }
+
+        tokio::time::sleep(Duration::from_secs(60 * 60)).await;
+    }
+}
+
+struct Agent;
+#[async_trait]
+impl Command for Agent {
+    fn name(&self) -> &'static str {
+        "agent"
+    }
+    fn needs_user(&self) -> bool {
+        false
+    }
+    async fn run(&self, program: &mut Program, _ctx: Option<&Context<'_>>) -> Result<(), SessionError> {
+        program.run_agent().await
+    }
+}
+
+struct Lock;
+#[async_trait]
+impl Command for Lock {
+    fn name(&self) -> &'static str {
+        "lock"
+    }
+    fn needs_user(&self) -> bool {
+        false
+    }
+    async fn run(&self, program: &mut Program, _ctx: Option<&Context<'_>>) -> Result<(), SessionError> {
+        program.lock().await
+    }
+}
+
+struct Unlock;
+#[async_trait]
+impl Command for Unlock {
+    fn name(&self) -> &'static str {
+        "unlock"
+    }
+    fn needs_user(&self) -> bool {
+        false
+    }
+    async fn run(&self, program: &mut Program, _ctx: Option<&Context<'_>>) -> Result<(), SessionError> {
+        program.unlock().await
+    }
+}
@@ -0,0 +1,43 @@
+use cli_42::Session;
+use cli_42::SessionError;
+
+/// Presents `client_cert_path` as mutual TLS and otherwise just honors
+/// reqwest's ambient proxy env vars.
+pub fn build_client(client_cert_path: Option<&str>) -> Result<reqwest::Client, SessionError> {
+    let mut builder = reqwest::Client::builder();
+    if let Some(path) = client_cert_path {
+        let bytes = std::fs::read(path)?;
+        let identity = reqwest::Identity::from_pem(&bytes)
+            .or_else(|_| reqwest::Identity::from_pkcs12_der(&bytes, ""))
+            .map_err(to_session_error)?;
+        builder = builder.identity(identity);
+    }
+    builder.build().map_err(to_session_error)
+}
+
+/// Issues the request through `client` (so `client_cert_path` and proxy
+/// config actually apply) using the session's cached token; on a 401 the
+/// token has likely expired, so this falls back to `session.call()`, which
+/// owns the refresh/retry behavior we'd otherwise lose by bypassing it.
+pub async fn fetch(
+    client: &reqwest::Client,
+    session: &mut Session,
+    url: &str,
+) -> Result<String, SessionError> {
+    let response = client
+        .get(url)
+        .bearer_auth(&session.get_token().access_token)
+        .send()
+        .await
+        .map_err(to_session_error)?;
+
+    if response.status() == reqwest::StatusCode::UNAUTHORIZED {
+        return session.call(url).await;
+    }
+
+    response.text().await.map_err(to_session_error)
+}
+
+fn to_session_error(e: reqwest::Error) -> SessionError {
+    std::io::Error::new(std::io::ErrorKind::Other, e.to_string()).into()
+}
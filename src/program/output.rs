@@ -0,0 +1,65 @@
+use crate::cli::Format;
+use serde_json::{Map, Value};
+
+pub struct Output {
+    format: Format,
+    fields: Vec<(String, String, Value)>,
+}
+
+impl Output {
+    pub fn new(format: Format) -> Self {
+        Output {
+            format,
+            fields: Vec::new(),
+        }
+    }
+
+    /// `key` is the machine-readable name (used by `json`/`kv`), `label` is
+    /// the human-readable one (used by `human`, matching the old `{:20}` layout).
+    pub fn push(&mut self, key: &str, label: &str, value: impl Into<Value>) -> &mut Self {
+        self.fields.push((key.to_string(), label.to_string(), value.into()));
+        self
+    }
+
+    pub fn print(&self) {
+        match self.format {
+            Format::Human => {
+                for (_, label, value) in &self.fields {
+                    println!("{:20}{}", label, scalar_to_string(value));
+                }
+            }
+            Format::Kv => {
+                for (key, _, value) in &self.fields {
+                    println!("{}={}", key, scalar_to_string(value));
+                }
+            }
+            Format::Json => {
+                let mut map = Map::new();
+                for (key, _, value) in &self.fields {
+                    map.insert(key.clone(), value.clone());
+                }
+                println!("{}", Value::Object(map));
+            }
+        }
+    }
+}
+
+pub fn scalar_to_string(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scalar_to_string_unwraps_strings_and_nulls() {
+        assert_eq!(scalar_to_string(&Value::String("a".to_string())), "a");
+        assert_eq!(scalar_to_string(&Value::Null), "");
+        assert_eq!(scalar_to_string(&Value::from(42)), "42");
+    }
+}
@@ -0,0 +1,30 @@
+use cli_42::SessionError;
+use keyring::Entry;
+
+const SERVICE: &str = "icp";
+const USERNAME: &str = "client_secret";
+
+/// Reads the OAuth client secret from the platform secret store, if present.
+pub fn load() -> Result<Option<String>, SessionError> {
+    let entry = Entry::new(SERVICE, USERNAME).map_err(to_session_error)?;
+    match entry.get_password() {
+        Ok(secret) => Ok(Some(secret)),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(to_session_error(e)),
+    }
+}
+
+pub fn store(secret: &str) -> Result<(), SessionError> {
+    let entry = Entry::new(SERVICE, USERNAME).map_err(to_session_error)?;
+    entry.set_password(secret).map_err(to_session_error)
+}
+
+/// Prompts on the terminal without echoing input back, for secrets that
+/// should never hit shell history or scrollback.
+pub fn prompt(message: &str) -> Result<String, SessionError> {
+    Ok(rpassword::prompt_password(message)?)
+}
+
+fn to_session_error(e: keyring::Error) -> SessionError {
+    std::io::Error::new(std::io::ErrorKind::Other, e.to_string()).into()
+}